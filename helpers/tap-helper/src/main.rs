@@ -13,11 +13,13 @@ use clap::{Parser, Subcommand};
 use nix::errno::Errno;
 use nix::fcntl::{open, OFlag};
 use nix::sys::stat::Mode;
-use nix::unistd::{close, getuid, getgid, Uid, Gid};
+use nix::sys::signal::kill;
+use nix::unistd::{close, getppid, getuid, getgid, Pid, Uid, Gid};
 use std::ffi::CString;
 use std::fs;
 use std::io::{Read, Write};
 use std::mem;
+use std::net::Ipv6Addr;
 use std::os::unix::io::RawFd;
 use std::path::Path;
 use std::process::exit;
@@ -28,25 +30,314 @@ const TUNSETIFF: libc::c_ulong = 0x400454ca;
 const TUNSETOWNER: libc::c_ulong = 0x400454cc;
 const TUNSETGROUP: libc::c_ulong = 0x400454ce;
 const TUNSETPERSIST: libc::c_ulong = 0x400454cb;
+const TUNGETFEATURES: libc::c_ulong = 0x800454cf;
+const TUNSETOFFLOAD: libc::c_ulong = 0x400454d0;
+const TUNSETVNETHDRSZ: libc::c_ulong = 0x400454d8;
 
 // TUN/TAP flags
 const IFF_TAP: libc::c_short = 0x0002;
 const IFF_NO_PI: libc::c_short = 0x1000;
 const IFF_VNET_HDR: libc::c_short = 0x4000;
+const IFF_MULTI_QUEUE: libc::c_short = 0x0100;
+
+// Offload feature bits accepted by TUNSETOFFLOAD (see linux/if_tun.h)
+const TUN_F_CSUM: libc::c_uint = 0x01;
+const TUN_F_TSO4: libc::c_uint = 0x02;
+const TUN_F_TSO6: libc::c_uint = 0x04;
+const TUN_F_UFO: libc::c_uint = 0x10;
+
+// Size of `struct virtio_net_hdr` without the mergeable-buffers extension.
+const VNET_HDR_SIZE: libc::c_int = 10;
 
 // Socket ioctl for interface operations
 const SIOCBRADDBR: libc::c_ulong = 0x89a0;
-const SIOCBRADDIF: libc::c_ulong = 0x89a2;
-const SIOCSIFFLAGS: libc::c_ulong = 0x8914;
 const SIOCGIFINDEX: libc::c_ulong = 0x8933;
-const SIOCGIFFLAGS: libc::c_ulong = 0x8913;
 const SIOCSIFADDR: libc::c_ulong = 0x8916;
 const SIOCSIFNETMASK: libc::c_ulong = 0x891c;
-
-const IFF_UP: libc::c_short = 0x1;
+const SIOCSIFHWADDR: libc::c_ulong = 0x8924;
 
 const IFNAMSIZ: usize = 16;
 
+// rtnetlink (NETLINK_ROUTE) constants used for link management. We avoid
+// shelling out to `ip` or using the legacy `SIOCBRADDIF` ioctl for bridge
+// enrollment, per the "syscalls and netlink only" policy above. Bridge
+// creation itself (`create_bridge`) still uses the `SIOCBRADDBR` ioctl.
+const NETLINK_ROUTE: libc::c_int = 0;
+
+const RTM_NEWLINK: u16 = 16;
+const RTM_DELLINK: u16 = 17;
+const RTM_SETLINK: u16 = 19;
+
+const NLM_F_REQUEST: u16 = 0x0001;
+const NLM_F_ACK: u16 = 0x0004;
+const NLM_F_EXCL: u16 = 0x0200;
+const NLM_F_CREATE: u16 = 0x0400;
+
+const NLMSG_ERROR: u16 = 2;
+
+const IFLA_IFNAME: u16 = 3;
+const IFLA_MASTER: u16 = 10;
+const IFLA_LINKINFO: u16 = 18;
+const IFLA_NET_NS_FD: u16 = 28;
+
+const IFLA_INFO_KIND: u16 = 1;
+const IFLA_INFO_DATA: u16 = 2;
+
+const VETH_INFO_PEER: u16 = 1;
+
+const IFF_LINK_UP: u32 = 0x1;
+
+/// Minimal mirror of `struct nlmsghdr`.
+#[repr(C)]
+struct NlMsgHdr {
+    nlmsg_len: u32,
+    nlmsg_type: u16,
+    nlmsg_flags: u16,
+    nlmsg_seq: u32,
+    nlmsg_pid: u32,
+}
+
+/// Minimal mirror of `struct ifinfomsg`.
+#[repr(C)]
+struct IfInfoMsg {
+    ifi_family: u8,
+    ifi_pad: u8,
+    ifi_type: u16,
+    ifi_index: i32,
+    ifi_flags: u32,
+    ifi_change: u32,
+}
+
+/// Minimal mirror of `struct sockaddr_nl`.
+#[repr(C)]
+struct SockAddrNl {
+    nl_family: u16,
+    nl_pad: u16,
+    nl_pid: u32,
+    nl_groups: u32,
+}
+
+/// Round a length up to the nearest 4-byte (`NLMSG_ALIGN`/`RTA_ALIGN`) boundary.
+fn nlmsg_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Append the raw bytes of a `#[repr(C)]` value to a netlink message buffer.
+fn push_struct<T>(buf: &mut Vec<u8>, value: &T) {
+    let bytes = unsafe {
+        std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>())
+    };
+    buf.extend_from_slice(bytes);
+}
+
+/// Append a TLV-encoded `rtattr` (`{u16 len, u16 type, payload}`, 4-byte aligned).
+fn push_rtattr(buf: &mut Vec<u8>, rta_type: u16, payload: &[u8]) {
+    let rta_len = (4 + payload.len()) as u16;
+    buf.extend_from_slice(&rta_len.to_ne_bytes());
+    buf.extend_from_slice(&rta_type.to_ne_bytes());
+    buf.extend_from_slice(payload);
+    let padded = nlmsg_align(buf.len());
+    buf.resize(padded, 0);
+}
+
+/// Open and bind a `NETLINK_ROUTE` socket for a single request/ack round-trip.
+fn open_netlink_socket() -> Result<RawFd, String> {
+    let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW, NETLINK_ROUTE) };
+    if fd < 0 {
+        return Err(format!("Failed to create netlink socket: {}", Errno::last()));
+    }
+
+    let addr = SockAddrNl {
+        nl_family: libc::AF_NETLINK as u16,
+        nl_pad: 0,
+        nl_pid: 0,
+        nl_groups: 0,
+    };
+
+    let result = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const SockAddrNl as *const libc::sockaddr,
+            mem::size_of::<SockAddrNl>() as u32,
+        )
+    };
+    if result < 0 {
+        let errno = Errno::last();
+        unsafe { libc::close(fd) };
+        return Err(format!("Failed to bind netlink socket: {}", errno));
+    }
+
+    Ok(fd)
+}
+
+/// Send an `nlmsghdr` + payload over `NETLINK_ROUTE` and wait for the `NLMSG_ERROR` ack,
+/// mapping a non-zero ack code to an error. `extra_flags` are OR'd in alongside the
+/// `NLM_F_REQUEST | NLM_F_ACK` every request needs (e.g. `NLM_F_CREATE | NLM_F_EXCL`).
+fn nl_request(msg_type: u16, extra_flags: u16, payload: &[u8]) -> Result<(), String> {
+    let sock_fd = open_netlink_socket()?;
+
+    let mut msg = Vec::with_capacity(mem::size_of::<NlMsgHdr>() + payload.len());
+    let hdr = NlMsgHdr {
+        nlmsg_len: (mem::size_of::<NlMsgHdr>() + payload.len()) as u32,
+        nlmsg_type: msg_type,
+        nlmsg_flags: NLM_F_REQUEST | NLM_F_ACK | extra_flags,
+        nlmsg_seq: 1,
+        nlmsg_pid: 0,
+    };
+    push_struct(&mut msg, &hdr);
+    msg.extend_from_slice(payload);
+
+    let sent = unsafe {
+        libc::send(sock_fd, msg.as_ptr() as *const libc::c_void, msg.len(), 0)
+    };
+    if sent < 0 {
+        let errno = Errno::last();
+        unsafe { libc::close(sock_fd) };
+        return Err(format!("Failed to send netlink request: {}", errno));
+    }
+
+    let mut reply = [0u8; 4096];
+    let received = unsafe {
+        libc::recv(
+            sock_fd,
+            reply.as_mut_ptr() as *mut libc::c_void,
+            reply.len(),
+            0,
+        )
+    };
+    unsafe { libc::close(sock_fd) };
+
+    if received < 0 {
+        return Err(format!("Failed to read netlink reply: {}", Errno::last()));
+    }
+
+    let hdr_len = mem::size_of::<NlMsgHdr>();
+    if (received as usize) < hdr_len + mem::size_of::<i32>() {
+        return Err("Netlink reply too short".to_string());
+    }
+
+    let reply_hdr = unsafe { &*(reply.as_ptr() as *const NlMsgHdr) };
+    if reply_hdr.nlmsg_type != NLMSG_ERROR {
+        return Err(format!(
+            "Unexpected netlink reply type: {}",
+            reply_hdr.nlmsg_type
+        ));
+    }
+
+    let error_code = i32::from_ne_bytes(reply[hdr_len..hdr_len + 4].try_into().unwrap());
+    if error_code != 0 {
+        return Err(format!(
+            "Netlink request failed: {}",
+            Errno::from_i32(-error_code)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Delete a link via `RTM_DELLINK`.
+fn nl_delete_link(name: &str) -> Result<(), String> {
+    let index = get_interface_index(name)?;
+    let ifi = IfInfoMsg {
+        ifi_family: libc::AF_UNSPEC as u8,
+        ifi_pad: 0,
+        ifi_type: 0,
+        ifi_index: index,
+        ifi_flags: 0,
+        ifi_change: 0,
+    };
+    let mut payload = Vec::new();
+    push_struct(&mut payload, &ifi);
+    nl_request(RTM_DELLINK, 0, &payload)
+}
+
+/// Enroll a link into a bridge via `RTM_SETLINK` carrying `IFLA_MASTER`.
+fn nl_set_master(link_name: &str, master_index: i32) -> Result<(), String> {
+    let index = get_interface_index(link_name)?;
+    let ifi = IfInfoMsg {
+        ifi_family: libc::AF_UNSPEC as u8,
+        ifi_pad: 0,
+        ifi_type: 0,
+        ifi_index: index,
+        ifi_flags: 0,
+        ifi_change: 0,
+    };
+    let mut payload = Vec::new();
+    push_struct(&mut payload, &ifi);
+    push_rtattr(&mut payload, IFLA_MASTER, &master_index.to_ne_bytes());
+    nl_request(RTM_SETLINK, 0, &payload)
+}
+
+/// Bring a link up via `RTM_NEWLINK` with `IFF_UP` set in `ifi_flags`/`ifi_change`.
+fn nl_set_link_up(name: &str) -> Result<(), String> {
+    let index = get_interface_index(name)?;
+    let ifi = IfInfoMsg {
+        ifi_family: libc::AF_UNSPEC as u8,
+        ifi_pad: 0,
+        ifi_type: 0,
+        ifi_index: index,
+        ifi_flags: IFF_LINK_UP,
+        ifi_change: IFF_LINK_UP,
+    };
+    let mut payload = Vec::new();
+    push_struct(&mut payload, &ifi);
+    nl_request(RTM_NEWLINK, 0, &payload)
+}
+
+/// Create a veth pair via `RTM_NEWLINK`: `host_name` is the end left on this host,
+/// `peer_name` is the end optionally moved into `target_ns_fd`'s network namespace.
+/// Encodes `IFLA_LINKINFO { IFLA_INFO_KIND = "veth", IFLA_INFO_DATA { VETH_INFO_PEER } }`,
+/// where the peer spec is itself an `ifinfomsg` plus its own attributes.
+fn nl_create_veth(
+    host_name: &str,
+    peer_name: &str,
+    target_ns_fd: Option<RawFd>,
+) -> Result<(), String> {
+    let mut peer_spec = Vec::new();
+    let peer_ifi = IfInfoMsg {
+        ifi_family: libc::AF_UNSPEC as u8,
+        ifi_pad: 0,
+        ifi_type: 0,
+        ifi_index: 0,
+        ifi_flags: 0,
+        ifi_change: 0,
+    };
+    push_struct(&mut peer_spec, &peer_ifi);
+
+    let mut peer_name_bytes = peer_name.as_bytes().to_vec();
+    peer_name_bytes.push(0);
+    push_rtattr(&mut peer_spec, IFLA_IFNAME, &peer_name_bytes);
+
+    if let Some(fd) = target_ns_fd {
+        push_rtattr(&mut peer_spec, IFLA_NET_NS_FD, &(fd as u32).to_ne_bytes());
+    }
+
+    let mut info_data = Vec::new();
+    push_rtattr(&mut info_data, VETH_INFO_PEER, &peer_spec);
+
+    let mut link_info = Vec::new();
+    push_rtattr(&mut link_info, IFLA_INFO_KIND, b"veth\0");
+    push_rtattr(&mut link_info, IFLA_INFO_DATA, &info_data);
+
+    let ifi = IfInfoMsg {
+        ifi_family: libc::AF_UNSPEC as u8,
+        ifi_pad: 0,
+        ifi_type: 0,
+        ifi_index: 0,
+        ifi_flags: 0,
+        ifi_change: 0,
+    };
+    let mut payload = Vec::new();
+    push_struct(&mut payload, &ifi);
+
+    let mut host_name_bytes = host_name.as_bytes().to_vec();
+    host_name_bytes.push(0);
+    push_rtattr(&mut payload, IFLA_IFNAME, &host_name_bytes);
+    push_rtattr(&mut payload, IFLA_LINKINFO, &link_info);
+
+    nl_request(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL, &payload)
+}
+
 /// TAP device request structure for ioctl
 #[repr(C)]
 struct IfReq {
@@ -62,6 +353,15 @@ union IfReqUnion {
     _padding: [u8; 24],
 }
 
+/// Mirror of `struct in6_ifreq`, used with `SIOCSIFADDR`/`SIOCDIFADDR` on an
+/// `AF_INET6` socket (distinct from the `ifreq`-based IPv4 address ioctls).
+#[repr(C)]
+struct In6IfReq {
+    ifr6_addr: libc::in6_addr,
+    ifr6_prefixlen: u32,
+    ifr6_ifindex: i32,
+}
+
 impl IfReq {
     fn new(name: &str) -> Result<Self, String> {
         if name.len() >= IFNAMSIZ {
@@ -89,6 +389,10 @@ impl IfReq {
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Skip dropping CAP_NET_ADMIN after the privileged work completes (debugging only)
+    #[arg(long, global = true)]
+    no_drop: bool,
 }
 
 #[derive(Subcommand)]
@@ -111,6 +415,18 @@ enum Commands {
         #[arg(long)]
         owner_gid: Option<u32>,
 
+        /// Negotiate virtio-net offloads (checksum/TSO/UFO) and set the vnet header size
+        #[arg(long)]
+        offload: bool,
+
+        /// Number of queues to open for multi-queue (multi-vCPU) VM networking
+        #[arg(long, default_value_t = 1)]
+        queues: u32,
+
+        /// MAC address for the TAP device (e.g., "02:aa:bb:cc:dd:ee"); auto-generated if omitted
+        #[arg(long)]
+        mac: Option<String>,
+
         /// Output format (json or text)
         #[arg(long, default_value = "json")]
         format: String,
@@ -126,15 +442,53 @@ enum Commands {
     /// Check if this helper has required capabilities
     CheckCaps,
 
+    /// Create a veth pair, optionally attaching the host end to a bridge and
+    /// moving the peer end into another network namespace
+    CreateVeth {
+        /// Name for the host-side end of the veth pair
+        #[arg(long)]
+        name: String,
+
+        /// Name for the peer end (created inside the target namespace, if given)
+        #[arg(long)]
+        peer_name: String,
+
+        /// Bridge to attach the host-side end to
+        #[arg(long)]
+        bridge: Option<String>,
+
+        /// PID whose network namespace the peer end should be moved into
+        #[arg(long)]
+        target_pid: Option<u32>,
+
+        /// Output format (json or text)
+        #[arg(long, default_value = "json")]
+        format: String,
+    },
+
+    /// List interfaces managed by this helper
+    List {
+        /// Only include interfaces whose name starts with this prefix
+        #[arg(long, default_value = "agentc-")]
+        prefix: String,
+    },
+
+    /// Reap persistent TAP devices whose owning process is no longer alive
+    Gc {
+        /// Only consider interfaces whose name starts with this prefix
+        #[arg(long, default_value = "agentc-")]
+        prefix: String,
+    },
+
     /// Setup bridge and basic networking infrastructure
     SetupBridge {
         /// Name for the bridge (e.g., "agentc-br0")
         #[arg(long)]
         name: String,
 
-        /// IP address for the bridge (e.g., "172.31.0.1/24")
+        /// IP address for the bridge, v4 or v6 CIDR (e.g., "172.31.0.1/24"); may be repeated
         #[arg(long)]
-        ip: String,
+        ip: Vec<String>,
     },
 }
 
@@ -142,6 +496,37 @@ enum Commands {
 struct CreateResult {
     success: bool,
     tap_name: String,
+    mac: String,
+    queue_count: u32,
+    offload_enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CreateVethResult {
+    success: bool,
+    name: String,
+    peer_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TapInfo {
+    name: String,
+    operstate: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bridge: Option<String>,
+    mac: String,
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+#[derive(Serialize)]
+struct GcResult {
+    success: bool,
+    reaped: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
 }
@@ -188,6 +573,56 @@ fn check_capabilities() -> bool {
     getuid().is_root()
 }
 
+// `_LINUX_CAPABILITY_VERSION_3`, the only version that still reports accurate
+// results for capabilities >= 32 via two `cap_user_data_t` entries.
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// Mirror of `struct __user_cap_header_struct`.
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+/// Mirror of `struct __user_cap_data_struct`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// Clear the effective/permitted/inheritable capability sets, then confirm via
+/// `check_capabilities`'s `/proc/self/status` parser that CAP_NET_ADMIN is really
+/// gone. We don't touch the bounding set here: the helper is installed via
+/// `setcap cap_net_admin+ep`, so it never holds CAP_SETPCAP and can't shrink it
+/// anyway — `PR_CAPBSET_DROP` would just fail with `EPERM`.
+fn drop_capabilities() -> Result<(), String> {
+    let header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let data = [CapUserData { effective: 0, permitted: 0, inheritable: 0 }; 2];
+
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_capset,
+            &header as *const CapUserHeader,
+            data.as_ptr(),
+        )
+    };
+    if result != 0 {
+        return Err(format!("Failed to drop capabilities: {}", Errno::last()));
+    }
+
+    if check_capabilities() {
+        return Err("CAP_NET_ADMIN still effective after capset".to_string());
+    }
+
+    Ok(())
+}
+
 /// Check if a network interface exists
 fn interface_exists(name: &str) -> bool {
     Path::new(&format!("/sys/class/net/{}", name)).exists()
@@ -202,6 +637,170 @@ fn get_interface_index(name: &str) -> Result<i32, String> {
         .map_err(|e| format!("Failed to parse ifindex: {}", e))
 }
 
+/// Read a single `/sys/class/net/<name>/statistics/<stat>` counter, defaulting to 0.
+fn read_interface_stat(name: &str, stat: &str) -> u64 {
+    fs::read_to_string(format!("/sys/class/net/{}/statistics/{}", name, stat))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Gather the reporting fields for one interface under `/sys/class/net`.
+fn read_tap_info(name: &str) -> TapInfo {
+    let operstate = fs::read_to_string(format!("/sys/class/net/{}/operstate", name))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    let mac = fs::read_to_string(format!("/sys/class/net/{}/address", name))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default();
+
+    let bridge = fs::read_link(format!("/sys/class/net/{}/master", name))
+        .ok()
+        .and_then(|p| p.file_name().map(|f| f.to_string_lossy().into_owned()));
+
+    TapInfo {
+        name: name.to_string(),
+        operstate,
+        bridge,
+        mac,
+        rx_bytes: read_interface_stat(name, "rx_bytes"),
+        tx_bytes: read_interface_stat(name, "tx_bytes"),
+    }
+}
+
+/// List interfaces under `/sys/class/net` whose name starts with `prefix`.
+fn list_taps(prefix: &str) -> Result<Vec<TapInfo>, String> {
+    let entries = fs::read_dir("/sys/class/net")
+        .map_err(|e| format!("Failed to read /sys/class/net: {}", e))?;
+
+    let mut taps = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read /sys/class/net entry: {}", e))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(prefix) {
+            taps.push(read_tap_info(&name));
+        }
+    }
+    Ok(taps)
+}
+
+/// Where `run_create` records the pid that asked for each TAP, so `gc` can check
+/// that exact process's liveness instead of guessing from the device's owner uid.
+const OWNER_PID_STATE_DIR: &str = "/run/agentc-tap";
+
+fn owner_pid_state_path(name: &str) -> String {
+    format!("{}/{}.pid", OWNER_PID_STATE_DIR, name)
+}
+
+/// Record `pid` as the owner of `name`, creating the state directory if needed.
+/// Best-effort: a failure here only degrades `gc`'s precision for this device,
+/// it doesn't affect the TAP itself, so callers treat it as a warning.
+fn write_owner_pid_state(name: &str, pid: Pid) -> Result<(), String> {
+    fs::create_dir_all(OWNER_PID_STATE_DIR)
+        .map_err(|e| format!("Failed to create {}: {}", OWNER_PID_STATE_DIR, e))?;
+    fs::write(owner_pid_state_path(name), pid.as_raw().to_string())
+        .map_err(|e| format!("Failed to write owner pid state for '{}': {}", name, e))
+}
+
+/// The pid recorded for `name` at `Create` time, if any.
+fn read_owner_pid_state(name: &str) -> Option<Pid> {
+    let content = fs::read_to_string(owner_pid_state_path(name)).ok()?;
+    content.trim().parse::<i32>().ok().map(Pid::from_raw)
+}
+
+fn remove_owner_pid_state(name: &str) {
+    let _ = fs::remove_file(owner_pid_state_path(name));
+}
+
+/// Whether `pid` is still alive, checked with a null signal rather than a `/proc`
+/// walk so it works under `hidepid=2` mounts: `kill` permission checks don't
+/// consult procfs visibility, only `ESRCH` means the process is actually gone.
+fn pid_is_alive(pid: Pid) -> bool {
+    !matches!(kill(pid, None), Err(Errno::ESRCH))
+}
+
+/// The TUN driver records the uid passed to `TUNSETOWNER` in this sysfs file
+/// (-1 if no owner was ever set). Used only as a fallback for devices with no
+/// recorded owner pid (e.g. created before this state dir existed).
+fn tap_owner_uid(name: &str) -> Option<u32> {
+    let content = fs::read_to_string(format!("/sys/class/net/{}/owner", name)).ok()?;
+    let uid: i64 = content.trim().parse().ok()?;
+    if uid < 0 {
+        None
+    } else {
+        Some(uid as u32)
+    }
+}
+
+/// Real uid of a running process, read from `/proc/<pid>/status`.
+fn process_uid(pid: &str) -> Option<u32> {
+    let content = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    for line in content.lines() {
+        if let Some(rest) = line.strip_prefix("Uid:") {
+            return rest.split_whitespace().next()?.parse().ok();
+        }
+    }
+    None
+}
+
+/// Whether any process on the system is currently running as `uid`. This is a
+/// coarse fallback: under a `hidepid=2` mount it can't see other users' pids at
+/// all and will under-report, and it can't tell two sandboxes sharing a uid
+/// apart. Only used when a device has no recorded owner pid to check directly.
+fn uid_has_live_process(uid: u32) -> bool {
+    let entries = match fs::read_dir("/proc") {
+        Ok(e) => e,
+        // If /proc can't be read, assume the owner might still be alive
+        // rather than risk reaping a live device.
+        Err(_) => return true,
+    };
+
+    for entry in entries.flatten() {
+        let pid = entry.file_name().to_string_lossy().into_owned();
+        if pid.chars().all(|c| c.is_ascii_digit()) && process_uid(&pid) == Some(uid) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Delete every interface under `prefix` whose owner is gone, returning the names
+/// that were reaped. Prefers the pid recorded at `Create` time (checked directly
+/// via `kill`); falls back to the coarser owner-uid scan in `uid_has_live_process`
+/// only for devices with no recorded pid.
+fn gc_orphaned_taps(prefix: &str) -> Result<Vec<String>, String> {
+    let entries = fs::read_dir("/sys/class/net")
+        .map_err(|e| format!("Failed to read /sys/class/net: {}", e))?;
+
+    let mut reaped = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read /sys/class/net entry: {}", e))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with(prefix) {
+            continue;
+        }
+
+        if let Some(pid) = read_owner_pid_state(&name) {
+            if pid_is_alive(pid) {
+                continue;
+            }
+        } else {
+            let Some(uid) = tap_owner_uid(&name) else {
+                continue;
+            };
+            if uid_has_live_process(uid) {
+                continue;
+            }
+        }
+
+        delete_interface(&name)?;
+        remove_owner_pid_state(&name);
+        reaped.push(name);
+    }
+    Ok(reaped)
+}
+
 /// Create a control socket for ioctl operations
 fn create_control_socket() -> Result<RawFd, String> {
     let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
@@ -211,15 +810,49 @@ fn create_control_socket() -> Result<RawFd, String> {
     Ok(fd)
 }
 
-/// Create a TAP device
-fn create_tap(name: &str, owner_uid: Uid, owner_gid: Gid) -> Result<RawFd, String> {
+/// Negotiate virtio-net offloads on an open TAP queue fd, masking the requested
+/// feature set against what the running kernel actually supports.
+fn negotiate_offload(tun_fd: RawFd) -> Result<(), String> {
+    let mut supported: libc::c_uint = 0;
+    unsafe {
+        if libc::ioctl(tun_fd, TUNGETFEATURES, &mut supported as *mut libc::c_uint) < 0 {
+            return Err(format!("Failed to read TUN features: {}", Errno::last()));
+        }
+    }
+
+    let requested = TUN_F_CSUM | TUN_F_TSO4 | TUN_F_TSO6 | TUN_F_UFO;
+    let offload = requested & supported;
+
+    unsafe {
+        if libc::ioctl(tun_fd, TUNSETOFFLOAD, offload as libc::c_ulong) < 0 {
+            return Err(format!("Failed to set TAP offloads: {}", Errno::last()));
+        }
+    }
+
+    unsafe {
+        let hdr_size = VNET_HDR_SIZE;
+        if libc::ioctl(tun_fd, TUNSETVNETHDRSZ, &hdr_size as *const libc::c_int) < 0 {
+            return Err(format!("Failed to set vnet header size: {}", Errno::last()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Open one TAP queue, attaching it to `name` (creating the device on the first queue).
+fn open_tap_queue(name: &str, multi_queue: bool, offload: bool) -> Result<RawFd, String> {
     let tun_fd = open(
         "/dev/net/tun",
         OFlag::O_RDWR | OFlag::O_CLOEXEC,
         Mode::empty(),
     ).map_err(|e| format!("Failed to open /dev/net/tun: {}", e))?;
 
-    let mut ifr = IfReq::with_flags(name, IFF_TAP | IFF_NO_PI | IFF_VNET_HDR)?;
+    let mut flags = IFF_TAP | IFF_NO_PI | IFF_VNET_HDR;
+    if multi_queue {
+        flags |= IFF_MULTI_QUEUE;
+    }
+
+    let mut ifr = IfReq::with_flags(name, flags)?;
 
     unsafe {
         if libc::ioctl(tun_fd, TUNSETIFF, &mut ifr as *mut IfReq) < 0 {
@@ -228,135 +861,165 @@ fn create_tap(name: &str, owner_uid: Uid, owner_gid: Gid) -> Result<RawFd, Strin
         }
     }
 
+    if offload {
+        if let Err(e) = negotiate_offload(tun_fd) {
+            let _ = close(tun_fd);
+            return Err(e);
+        }
+    }
+
+    Ok(tun_fd)
+}
+
+/// Create a TAP device, optionally as `queues` multi-queue fds with virtio-net
+/// offloads negotiated on each one.
+fn create_tap(
+    name: &str,
+    owner_uid: Uid,
+    owner_gid: Gid,
+    queues: u32,
+    offload: bool,
+) -> Result<Vec<RawFd>, String> {
+    let queue_count = queues.max(1);
+    let multi_queue = queue_count > 1;
+
+    let mut fds = Vec::with_capacity(queue_count as usize);
+    for _ in 0..queue_count {
+        match open_tap_queue(name, multi_queue, offload) {
+            Ok(fd) => fds.push(fd),
+            Err(e) => {
+                for fd in fds {
+                    let _ = close(fd);
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    let first_fd = fds[0];
+
     unsafe {
         let uid = owner_uid.as_raw() as libc::c_ulong;
-        if libc::ioctl(tun_fd, TUNSETOWNER, uid) < 0 {
-            let _ = close(tun_fd);
-            return Err(format!("Failed to set TAP owner: {}", Errno::last()));
+        if libc::ioctl(first_fd, TUNSETOWNER, uid) < 0 {
+            let err = format!("Failed to set TAP owner: {}", Errno::last());
+            for fd in fds {
+                let _ = close(fd);
+            }
+            return Err(err);
         }
     }
 
     unsafe {
         let gid = owner_gid.as_raw() as libc::c_ulong;
-        if libc::ioctl(tun_fd, TUNSETGROUP, gid) < 0 {
-            let _ = close(tun_fd);
-            return Err(format!("Failed to set TAP group: {}", Errno::last()));
+        if libc::ioctl(first_fd, TUNSETGROUP, gid) < 0 {
+            let err = format!("Failed to set TAP group: {}", Errno::last());
+            for fd in fds {
+                let _ = close(fd);
+            }
+            return Err(err);
         }
     }
 
     unsafe {
-        if libc::ioctl(tun_fd, TUNSETPERSIST, 1 as libc::c_ulong) < 0 {
-            let _ = close(tun_fd);
-            return Err(format!("Failed to set TAP persistence: {}", Errno::last()));
+        if libc::ioctl(first_fd, TUNSETPERSIST, 1 as libc::c_ulong) < 0 {
+            let err = format!("Failed to set TAP persistence: {}", Errno::last());
+            for fd in fds {
+                let _ = close(fd);
+            }
+            return Err(err);
         }
     }
 
-    Ok(tun_fd)
+    Ok(fds)
 }
 
-/// Add interface to bridge using ioctl
-fn add_to_bridge(tap_name: &str, bridge_name: &str) -> Result<(), String> {
-    if !interface_exists(bridge_name) {
-        return Err(format!("Bridge '{}' does not exist", bridge_name));
+/// Parse a colon-separated MAC address string (e.g. "02:aa:bb:cc:dd:ee") into 6 bytes.
+fn parse_mac(mac: &str) -> Result<[u8; 6], String> {
+    let parts: Vec<&str> = mac.split(':').collect();
+    if parts.len() != 6 {
+        return Err(format!(
+            "Invalid MAC address '{}': expected 6 colon-separated hex octets",
+            mac
+        ));
     }
 
-    let tap_index = get_interface_index(tap_name)?;
-    let sock_fd = create_control_socket()?;
-
-    let mut ifr = IfReq::new(bridge_name)?;
-    ifr.ifr_ifru.ifr_ifindex = tap_index;
+    let mut bytes = [0u8; 6];
+    for (i, part) in parts.iter().enumerate() {
+        bytes[i] = u8::from_str_radix(part, 16)
+            .map_err(|_| format!("Invalid MAC address octet: '{}'", part))?;
+    }
+    Ok(bytes)
+}
 
-    let result = unsafe { libc::ioctl(sock_fd, SIOCBRADDIF, &mut ifr as *mut IfReq) };
-    unsafe { libc::close(sock_fd) };
+/// Format 6 raw bytes as a colon-separated MAC address string.
+fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
 
-    if result < 0 {
-        return Err(format!("Failed to add TAP to bridge: {}", Errno::last()));
+/// Derive a reproducible, locally-administered unicast MAC address from a device name,
+/// so re-creating the same TAP always yields the same link-layer address.
+fn generate_mac(name: &str) -> [u8; 6] {
+    // FNV-1a: no extra dependency, stable across runs and platforms.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in name.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
     }
 
-    Ok(())
+    let mut mac = [0u8; 6];
+    mac.copy_from_slice(&hash.to_be_bytes()[0..6]);
+    mac[0] = (mac[0] & !0x01) | 0x02; // locally administered, unicast
+    mac
 }
 
-/// Bring interface up using ioctl
-fn bring_up(name: &str) -> Result<(), String> {
+/// Set a device's hardware (MAC) address via `SIOCSIFHWADDR`.
+fn set_mac_address(name: &str, mac: &[u8; 6]) -> Result<(), String> {
     let sock_fd = create_control_socket()?;
     let mut ifr = IfReq::new(name)?;
 
-    // Get current flags
-    let result = unsafe { libc::ioctl(sock_fd, SIOCGIFFLAGS, &mut ifr as *mut IfReq) };
-    if result < 0 {
-        unsafe { libc::close(sock_fd) };
-        return Err(format!("Failed to get interface flags: {}", Errno::last()));
-    }
-
-    // Add UP flag
     unsafe {
-        ifr.ifr_ifru.ifr_flags |= IFF_UP;
+        let addr = &mut ifr.ifr_ifru.ifr_addr as *mut libc::sockaddr;
+        (*addr).sa_family = libc::ARPHRD_ETHER as libc::sa_family_t;
+        for (i, byte) in mac.iter().enumerate() {
+            (*addr).sa_data[i] = *byte as libc::c_char;
+        }
     }
 
-    // Set flags
-    let result = unsafe { libc::ioctl(sock_fd, SIOCSIFFLAGS, &mut ifr as *mut IfReq) };
+    let result = unsafe { libc::ioctl(sock_fd, SIOCSIFHWADDR, &mut ifr as *mut IfReq) };
     unsafe { libc::close(sock_fd) };
 
     if result < 0 {
-        return Err(format!("Failed to bring up interface: {}", Errno::last()));
+        return Err(format!("Failed to set MAC address: {}", Errno::last()));
     }
 
     Ok(())
 }
 
-/// Delete interface by writing to sysfs (simpler than netlink)
-fn delete_interface(name: &str) -> Result<(), String> {
-    // For TAP devices, we can delete by clearing persistence and closing
-    // But the simplest is to use the /sys interface
-    let path = format!("/sys/class/net/{}/operstate", name);
-    if !Path::new(&path).exists() {
-        return Err(format!("Interface '{}' does not exist", name));
+/// Add interface to bridge via rtnetlink
+fn add_to_bridge(tap_name: &str, bridge_name: &str) -> Result<(), String> {
+    if !interface_exists(bridge_name) {
+        return Err(format!("Bridge '{}' does not exist", bridge_name));
     }
 
-    // Open the TUN device and clear persistence to delete the TAP
-    let tun_fd = match open("/dev/net/tun", OFlag::O_RDWR, Mode::empty()) {
-        Ok(fd) => fd,
-        Err(_) => {
-            // Fall back to ip command if we can't access /dev/net/tun
-            let output = std::process::Command::new("ip")
-                .args(["link", "delete", name])
-                .output()
-                .map_err(|e| format!("Failed to execute ip command: {}", e))?;
-            if !output.status.success() {
-                return Err(format!("Failed to delete interface: {}",
-                    String::from_utf8_lossy(&output.stderr)));
-            }
-            return Ok(());
-        }
-    };
-
-    let mut ifr = IfReq::with_flags(name, IFF_TAP | IFF_NO_PI)?;
+    let master_index = get_interface_index(bridge_name)?;
+    nl_set_master(tap_name, master_index)
+}
 
-    unsafe {
-        // Try to attach to existing TAP
-        if libc::ioctl(tun_fd, TUNSETIFF, &mut ifr as *mut IfReq) < 0 {
-            let _ = close(tun_fd);
-            // If we can't attach, try ip command
-            let output = std::process::Command::new("ip")
-                .args(["link", "delete", name])
-                .output()
-                .map_err(|e| format!("Failed to execute ip command: {}", e))?;
-            if !output.status.success() {
-                return Err(format!("Failed to delete interface: {}",
-                    String::from_utf8_lossy(&output.stderr)));
-            }
-            return Ok(());
-        }
+/// Bring interface up via rtnetlink
+fn bring_up(name: &str) -> Result<(), String> {
+    nl_set_link_up(name)
+}
 
-        // Clear persistence
-        if libc::ioctl(tun_fd, TUNSETPERSIST, 0 as libc::c_ulong) < 0 {
-            let _ = close(tun_fd);
-            return Err(format!("Failed to clear TAP persistence: {}", Errno::last()));
-        }
+/// Delete interface via rtnetlink
+fn delete_interface(name: &str) -> Result<(), String> {
+    if !interface_exists(name) {
+        return Err(format!("Interface '{}' does not exist", name));
     }
 
-    let _ = close(tun_fd);
-    Ok(())
+    nl_delete_link(name)
 }
 
 /// Create bridge using ioctl
@@ -383,13 +1046,17 @@ fn create_bridge(name: &str) -> Result<(), String> {
 fn set_ip_address(name: &str, ip: &str) -> Result<(), String> {
     let parts: Vec<&str> = ip.split('/').collect();
     if parts.len() != 2 {
-        return Err("IP must be in CIDR format (e.g., 172.31.0.1/24)".to_string());
+        return Err("IP must be in CIDR format (e.g., 172.31.0.1/24 or fd00::1/64)".to_string());
     }
 
     let addr_str = parts[0];
     let prefix_len: u32 = parts[1].parse()
         .map_err(|_| "Invalid prefix length".to_string())?;
 
+    if let Ok(addr) = addr_str.parse::<Ipv6Addr>() {
+        return set_ipv6_address(name, addr, prefix_len);
+    }
+
     // Parse IP address
     let octets: Vec<u8> = addr_str.split('.')
         .map(|s| s.parse().map_err(|_| "Invalid IP octet"))
@@ -399,6 +1066,10 @@ fn set_ip_address(name: &str, ip: &str) -> Result<(), String> {
         return Err("Invalid IP address format".to_string());
     }
 
+    if prefix_len > 32 {
+        return Err("IPv4 prefix length must be between 0 and 32".to_string());
+    }
+
     let sock_fd = create_control_socket()?;
     let mut ifr = IfReq::new(name)?;
 
@@ -443,6 +1114,41 @@ fn set_ip_address(name: &str, ip: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Set an IPv6 address on an interface via `SIOCSIFADDR` on an `AF_INET6` socket,
+/// using `in6_ifreq` (prefix length and ifindex, rather than a separate netmask ioctl).
+fn set_ipv6_address(name: &str, addr: Ipv6Addr, prefix_len: u32) -> Result<(), String> {
+    if prefix_len > 128 {
+        return Err("IPv6 prefix length must be between 0 and 128".to_string());
+    }
+
+    let index = get_interface_index(name)?;
+
+    let sock_fd = unsafe { libc::socket(libc::AF_INET6, libc::SOCK_DGRAM, 0) };
+    if sock_fd < 0 {
+        return Err(format!("Failed to create AF_INET6 socket: {}", Errno::last()));
+    }
+
+    let req = In6IfReq {
+        ifr6_addr: libc::in6_addr {
+            s6_addr: addr.octets(),
+        },
+        ifr6_prefixlen: prefix_len,
+        ifr6_ifindex: index,
+    };
+
+    let result = unsafe { libc::ioctl(sock_fd, SIOCSIFADDR, &req as *const In6IfReq) };
+    unsafe { libc::close(sock_fd) };
+
+    if result < 0 {
+        let errno = Errno::last();
+        if errno != Errno::EEXIST {
+            return Err(format!("Failed to set IPv6 address: {}", errno));
+        }
+    }
+
+    Ok(())
+}
+
 fn print_error(msg: &str, format: &str) {
     if format == "json" {
         let result = ErrorResult {
@@ -455,141 +1161,345 @@ fn print_error(msg: &str, format: &str) {
     }
 }
 
-fn main() {
-    let cli = Cli::parse();
+// One argument per `Commands::Create` field; a struct wrapper would just
+// duplicate the variant's fields for no benefit.
+#[allow(clippy::too_many_arguments)]
+fn run_create(name: String, bridge: String, owner_uid: Option<u32>, owner_gid: Option<u32>,
+    offload: bool, queues: u32, mac: Option<String>, format: String) -> Result<(), ()> {
+    if let Err(e) = validate_interface_name(&name) {
+        print_error(&e, &format);
+        return Err(());
+    }
+    if let Err(e) = validate_interface_name(&bridge) {
+        print_error(&e, &format);
+        return Err(());
+    }
 
-    match cli.command {
-        Commands::Create { name, bridge, owner_uid, owner_gid, format } => {
-            if let Err(e) = validate_interface_name(&name) {
-                print_error(&e, &format);
-                exit(1);
-            }
-            if let Err(e) = validate_interface_name(&bridge) {
+    if !check_capabilities() {
+        print_error(
+            "Missing CAP_NET_ADMIN capability. Install with: sudo setcap cap_net_admin+ep <binary>",
+            &format
+        );
+        return Err(());
+    }
+
+    if interface_exists(&name) {
+        print_error(&format!("TAP device '{}' already exists", name), &format);
+        return Err(());
+    }
+
+    if queues == 0 {
+        print_error("--queues must be at least 1", &format);
+        return Err(());
+    }
+
+    let mac_bytes = match &mac {
+        Some(m) => match parse_mac(m) {
+            Ok(bytes) => bytes,
+            Err(e) => {
                 print_error(&e, &format);
-                exit(1);
+                return Err(());
             }
+        },
+        None => generate_mac(&name),
+    };
 
-            if !check_capabilities() {
-                print_error(
-                    "Missing CAP_NET_ADMIN capability. Install with: sudo setcap cap_net_admin+ep <binary>",
-                    &format
-                );
-                exit(1);
-            }
+    let uid = Uid::from_raw(owner_uid.unwrap_or_else(|| getuid().as_raw()));
+    let gid = Gid::from_raw(owner_gid.unwrap_or_else(|| getgid().as_raw()));
 
-            if interface_exists(&name) {
-                print_error(&format!("TAP device '{}' already exists", name), &format);
-                exit(1);
-            }
+    let tap_fds = match create_tap(&name, uid, gid, queues, offload) {
+        Ok(fds) => fds,
+        Err(e) => {
+            print_error(&e, &format);
+            return Err(());
+        }
+    };
+
+    for fd in &tap_fds {
+        let _ = close(*fd);
+    }
+
+    // Assign MAC address
+    if let Err(e) = set_mac_address(&name, &mac_bytes) {
+        let _ = delete_interface(&name);
+        print_error(&e, &format);
+        return Err(());
+    }
+
+    // Add to bridge
+    if let Err(e) = add_to_bridge(&name, &bridge) {
+        let _ = delete_interface(&name);
+        print_error(&e, &format);
+        return Err(());
+    }
+
+    // Bring up interface
+    if let Err(e) = bring_up(&name) {
+        let _ = delete_interface(&name);
+        print_error(&e, &format);
+        return Err(());
+    }
+
+    // Record our caller's pid so `gc` can later check that specific process's
+    // liveness instead of guessing from the device's owner uid. Best-effort:
+    // the TAP is fully usable even if this fails, it just won't be precisely
+    // collectible later.
+    if let Err(e) = write_owner_pid_state(&name, getppid()) {
+        eprintln!("Warning: {}", e);
+    }
+
+    if format == "json" {
+        let result = CreateResult {
+            success: true,
+            tap_name: name,
+            mac: format_mac(&mac_bytes),
+            queue_count: queues,
+            offload_enabled: offload,
+            error: None,
+        };
+        println!("{}", serde_json::to_string(&result).unwrap());
+    } else {
+        println!("Created TAP device: {} ({} queue(s), mac {}{})", name, queues,
+            format_mac(&mac_bytes),
+            if offload { ", offloads negotiated" } else { "" });
+    }
+    Ok(())
+}
+
+fn run_delete(name: String) -> Result<(), ()> {
+    if let Err(e) = validate_interface_name(&name) {
+        eprintln!("Error: {}", e);
+        return Err(());
+    }
+
+    if !check_capabilities() {
+        eprintln!("Error: Missing CAP_NET_ADMIN capability");
+        return Err(());
+    }
+
+    if !interface_exists(&name) {
+        // Not an error if it doesn't exist
+        println!("TAP device '{}' does not exist", name);
+        return Ok(());
+    }
 
-            let uid = Uid::from_raw(owner_uid.unwrap_or_else(|| getuid().as_raw()));
-            let gid = Gid::from_raw(owner_gid.unwrap_or_else(|| getgid().as_raw()));
+    if let Err(e) = delete_interface(&name) {
+        eprintln!("Error: {}", e);
+        return Err(());
+    }
+    remove_owner_pid_state(&name);
+
+    println!("Deleted TAP device: {}", name);
+    Ok(())
+}
+
+fn run_check_caps() -> Result<(), ()> {
+    if check_capabilities() {
+        println!("CAP_NET_ADMIN: yes");
+        Ok(())
+    } else {
+        println!("CAP_NET_ADMIN: no");
+        println!("Install with: sudo setcap cap_net_admin+ep {}",
+            std::env::args().next().unwrap_or_default());
+        Err(())
+    }
+}
+
+fn run_create_veth(name: String, peer_name: String, bridge: Option<String>,
+    target_pid: Option<u32>, format: String) -> Result<(), ()> {
+    if let Err(e) = validate_interface_name(&name) {
+        print_error(&e, &format);
+        return Err(());
+    }
+    if let Err(e) = validate_interface_name(&peer_name) {
+        print_error(&e, &format);
+        return Err(());
+    }
+    if let Some(ref b) = bridge {
+        if let Err(e) = validate_interface_name(b) {
+            print_error(&e, &format);
+            return Err(());
+        }
+    }
 
-            let tap_fd = match create_tap(&name, uid, gid) {
-                Ok(fd) => fd,
+    if !check_capabilities() {
+        print_error(
+            "Missing CAP_NET_ADMIN capability. Install with: sudo setcap cap_net_admin+ep <binary>",
+            &format
+        );
+        return Err(());
+    }
+
+    if interface_exists(&name) {
+        print_error(&format!("Interface '{}' already exists", name), &format);
+        return Err(());
+    }
+
+    let ns_fd = match target_pid {
+        Some(pid) => {
+            let ns_path = format!("/proc/{}/ns/net", pid);
+            match open(ns_path.as_str(), OFlag::O_RDONLY, Mode::empty()) {
+                Ok(fd) => Some(fd),
                 Err(e) => {
-                    print_error(&e, &format);
-                    exit(1);
+                    print_error(&format!("Failed to open netns for pid {}: {}", pid, e), &format);
+                    return Err(());
                 }
-            };
+            }
+        }
+        None => None,
+    };
 
-            let _ = close(tap_fd);
+    if let Err(e) = nl_create_veth(&name, &peer_name, ns_fd) {
+        if let Some(fd) = ns_fd {
+            let _ = close(fd);
+        }
+        print_error(&e, &format);
+        return Err(());
+    }
 
-            // Add to bridge
-            if let Err(e) = add_to_bridge(&name, &bridge) {
-                let _ = delete_interface(&name);
-                print_error(&e, &format);
-                exit(1);
-            }
+    if let Some(fd) = ns_fd {
+        let _ = close(fd);
+    }
 
-            // Bring up interface
-            if let Err(e) = bring_up(&name) {
-                let _ = delete_interface(&name);
-                print_error(&e, &format);
-                exit(1);
-            }
+    if let Err(e) = bring_up(&name) {
+        let _ = delete_interface(&name);
+        print_error(&e, &format);
+        return Err(());
+    }
 
-            if format == "json" {
-                let result = CreateResult {
-                    success: true,
-                    tap_name: name,
-                    error: None,
-                };
-                println!("{}", serde_json::to_string(&result).unwrap());
-            } else {
-                println!("Created TAP device: {}", name);
-            }
+    // Only the host end is ours to bring up once the peer has moved into
+    // another namespace; when it hasn't moved, both ends still live here and
+    // a veth pair isn't usable with only one side up.
+    if target_pid.is_none() {
+        if let Err(e) = bring_up(&peer_name) {
+            let _ = delete_interface(&name);
+            print_error(&e, &format);
+            return Err(());
         }
+    }
 
-        Commands::Delete { name } => {
-            if let Err(e) = validate_interface_name(&name) {
-                eprintln!("Error: {}", e);
-                exit(1);
-            }
+    if let Some(b) = &bridge {
+        if let Err(e) = add_to_bridge(&name, b) {
+            let _ = delete_interface(&name);
+            print_error(&e, &format);
+            return Err(());
+        }
+    }
 
-            if !check_capabilities() {
-                eprintln!("Error: Missing CAP_NET_ADMIN capability");
-                exit(1);
-            }
+    if format == "json" {
+        let result = CreateVethResult {
+            success: true,
+            name,
+            peer_name,
+            error: None,
+        };
+        println!("{}", serde_json::to_string(&result).unwrap());
+    } else {
+        println!("Created veth pair: {} <-> {}", name, peer_name);
+    }
+    Ok(())
+}
 
-            if !interface_exists(&name) {
-                // Not an error if it doesn't exist
-                println!("TAP device '{}' does not exist", name);
-                exit(0);
-            }
+fn run_list(prefix: String) -> Result<(), ()> {
+    match list_taps(&prefix) {
+        Ok(taps) => {
+            println!("{}", serde_json::to_string(&taps).unwrap());
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            Err(())
+        }
+    }
+}
 
-            if let Err(e) = delete_interface(&name) {
-                eprintln!("Error: {}", e);
-                exit(1);
-            }
+fn run_gc(prefix: String) -> Result<(), ()> {
+    if !check_capabilities() {
+        eprintln!("Error: Missing CAP_NET_ADMIN capability");
+        return Err(());
+    }
 
-            println!("Deleted TAP device: {}", name);
+    match gc_orphaned_taps(&prefix) {
+        Ok(reaped) => {
+            let result = GcResult { success: true, reaped, error: None };
+            println!("{}", serde_json::to_string(&result).unwrap());
+            Ok(())
+        }
+        Err(e) => {
+            let result = GcResult { success: false, reaped: Vec::new(), error: Some(e) };
+            println!("{}", serde_json::to_string(&result).unwrap());
+            Err(())
         }
+    }
+}
 
-        Commands::CheckCaps => {
-            if check_capabilities() {
-                println!("CAP_NET_ADMIN: yes");
-                exit(0);
-            } else {
-                println!("CAP_NET_ADMIN: no");
-                println!("Install with: sudo setcap cap_net_admin+ep {}",
-                    std::env::args().next().unwrap_or_default());
-                exit(1);
-            }
+fn run_setup_bridge(name: String, ip: Vec<String>) -> Result<(), ()> {
+    if let Err(e) = validate_interface_name(&name) {
+        eprintln!("Error: {}", e);
+        return Err(());
+    }
+
+    if !check_capabilities() {
+        eprintln!("Error: Missing CAP_NET_ADMIN capability");
+        return Err(());
+    }
+
+    // Create bridge if it doesn't exist
+    if !interface_exists(&name) {
+        if let Err(e) = create_bridge(&name) {
+            eprintln!("Error creating bridge: {}", e);
+            return Err(());
         }
+    }
 
-        Commands::SetupBridge { name, ip } => {
-            if let Err(e) = validate_interface_name(&name) {
-                eprintln!("Error: {}", e);
-                exit(1);
-            }
+    if ip.is_empty() {
+        eprintln!("Error: at least one --ip must be given");
+        return Err(());
+    }
 
-            if !check_capabilities() {
-                eprintln!("Error: Missing CAP_NET_ADMIN capability");
-                exit(1);
-            }
+    // Set IP address(es) - v4 and/or v6, one set_ip_address call per address
+    for addr in &ip {
+        if let Err(e) = set_ip_address(&name, addr) {
+            eprintln!("Error setting IP {}: {}", addr, e);
+            return Err(());
+        }
+    }
 
-            // Create bridge if it doesn't exist
-            if !interface_exists(&name) {
-                if let Err(e) = create_bridge(&name) {
-                    eprintln!("Error creating bridge: {}", e);
-                    exit(1);
-                }
-            }
+    // Bring up
+    if let Err(e) = bring_up(&name) {
+        eprintln!("Error bringing up bridge: {}", e);
+        return Err(());
+    }
 
-            // Set IP address
-            if let Err(e) = set_ip_address(&name, &ip) {
-                eprintln!("Error setting IP: {}", e);
-                exit(1);
-            }
+    println!("Bridge '{}' configured with IP(s) {}", name, ip.join(", "));
+    Ok(())
+}
 
-            // Bring up
-            if let Err(e) = bring_up(&name) {
-                eprintln!("Error bringing up bridge: {}", e);
-                exit(1);
-            }
+fn main() {
+    let cli = Cli::parse();
+    let no_drop = cli.no_drop;
+
+    let result = match cli.command {
+        Commands::Create { name, bridge, owner_uid, owner_gid, offload, queues, mac, format } =>
+            run_create(name, bridge, owner_uid, owner_gid, offload, queues, mac, format),
+        Commands::Delete { name } => run_delete(name),
+        Commands::CheckCaps => run_check_caps(),
+        Commands::CreateVeth { name, peer_name, bridge, target_pid, format } =>
+            run_create_veth(name, peer_name, bridge, target_pid, format),
+        Commands::List { prefix } => run_list(prefix),
+        Commands::Gc { prefix } => run_gc(prefix),
+        Commands::SetupBridge { name, ip } => run_setup_bridge(name, ip),
+    };
 
-            println!("Bridge '{}' configured with IP {}", name, ip);
+    // Drop capabilities exactly once, before exiting on either path, so a failed
+    // or short-circuited command can't leave the process holding CAP_NET_ADMIN.
+    if !no_drop {
+        if let Err(e) = drop_capabilities() {
+            eprintln!("Warning: failed to drop capabilities: {}", e);
         }
     }
+
+    if result.is_err() {
+        exit(1);
+    }
 }